@@ -1,21 +1,40 @@
+#[cfg(not(target_arch = "wasm32"))]
 use std::{f32::consts::TAU, fs::File, io::Write};
 
 use glam::DVec2;
+#[cfg(not(target_arch = "wasm32"))]
+use hound::{SampleFormat, WavSpec, WavWriter};
+#[cfg(not(target_arch = "wasm32"))]
 use pretty::RcDoc;
-use raqote::{DrawOptions, DrawTarget, PathBuilder, SolidSource, Transform};
+use raqote::SolidSource;
+#[cfg(not(target_arch = "wasm32"))]
+use raqote::{DrawOptions, DrawTarget, PathBuilder, Transform};
 use rayon::{
     iter::{ParallelBridge, ParallelIterator},
     slice::ParallelSlice,
 };
 use rustfft::{FftPlanner, num_complex::Complex64};
 use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
 
+// not available on wasm32, where `bake_orbit` is the entry point instead
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
     let config: Config = toml::from_str(&std::fs::read_to_string("orbits.toml").unwrap()).unwrap();
 
     let sim_config = SimulationConfig {
         frames: 140,
         subframes: 100,
+        integrator: Integrator::ForestRuth,
+        energy_drift_tolerance: 1e-7,
+        blend_time_reversed: true,
+    };
+
+    let audio_config = AudioConfig {
+        sample_rate: 44100,
+        duration: 4.0,
+        base_frequency: 220.0,
     };
 
     // bake each orbit in the config in parallel
@@ -23,7 +42,7 @@ fn main() {
         .orbit
         .iter()
         .par_bridge()
-        .map(|orbit| bake(&sim_config, orbit))
+        .map(|orbit| bake(&sim_config, &audio_config, orbit))
         .collect();
 
     let doc = RcDoc::text("orbits = ").append(
@@ -39,16 +58,28 @@ fn main() {
     stdout.flush().unwrap();
 }
 
-pub fn bake(sim_config: &SimulationConfig, orbit_config: &OrbitConfig) -> BakedOrbit {
+// shared by the native CLI and the wasm32 entry point below; no file I/O
+pub fn bake_core(
+    sim_config: &SimulationConfig,
+    orbit_config: &OrbitConfig,
+) -> (Orbit, Vec<Vec<DVec2>>, Vec<BakedBody>) {
     let orbit = orbit_config.to_orbit();
 
     let simulated = simulate_closed(sim_config, &orbit);
 
     let mut baked_bodies = analyze(&simulated);
+    optimize_bodies(&mut baked_bodies, orbit_config.optimize);
 
-    baked_bodies
-        .iter_mut()
-        .for_each(|body| body.optimize(0.001));
+    (orbit, simulated, baked_bodies)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn bake(
+    sim_config: &SimulationConfig,
+    audio_config: &AudioConfig,
+    orbit_config: &OrbitConfig,
+) -> BakedOrbit {
+    let (orbit, simulated, baked_bodies) = bake_core(sim_config, orbit_config);
 
     let by_body = transpose(&simulated, Clone::clone);
 
@@ -62,6 +93,7 @@ pub fn bake(sim_config: &SimulationConfig, orbit_config: &OrbitConfig) -> BakedO
 
     let compressed = transpose(&compressed, Clone::clone);
     render(sim_config, &orbit, &compressed);
+    render_audio(audio_config, &orbit, &baked_bodies);
 
     BakedOrbit {
         name: orbit_config.name.clone(),
@@ -71,6 +103,25 @@ pub fn bake(sim_config: &SimulationConfig, orbit_config: &OrbitConfig) -> BakedO
     }
 }
 
+// lets a web UI bake orbits live instead of shipping precomputed constants
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn bake_orbit(config_js: JsValue, sim_js: JsValue) -> JsValue {
+    let orbit_config: OrbitConfig = serde_wasm_bindgen::from_value(config_js).unwrap();
+    let sim_config: SimulationConfig = serde_wasm_bindgen::from_value(sim_js).unwrap();
+
+    let (_orbit, _simulated, baked_bodies) = bake_core(&sim_config, &orbit_config);
+
+    let baked = BakedOrbit {
+        name: orbit_config.name.clone(),
+        period: orbit_config.period,
+        energy: orbit_config.energy,
+        bodies: baked_bodies,
+    };
+
+    serde_wasm_bindgen::to_value(&baked).unwrap()
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     pub orbit: Vec<OrbitConfig>,
@@ -84,6 +135,17 @@ pub struct OrbitConfig {
     pub masses: Vec<f64>,
     pub positions: Vec<DVec2>,
     pub velocities: Vec<DVec2>,
+    pub optimize: OptimizeTarget,
+}
+
+// a per-orbit rate-distortion budget applied by `optimize_bodies` across
+// all of the orbit's bodies combined: `Error` targets an RMS reconstruction
+// error, `Components` targets an exact retained-component count
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptimizeTarget {
+    Error(f64),
+    Components(usize),
 }
 
 impl OrbitConfig {
@@ -126,10 +188,15 @@ pub fn analyze(positions: &[Vec<DVec2>]) -> Vec<BakedBody> {
         im: pos.y,
     });
 
+    // rayon's global thread pool needs real OS threads, which wasm32 doesn't
+    // have without extra plumbing (wasm-bindgen-rayon); fall back to serial
+    #[cfg(not(target_arch = "wasm32"))]
     freqs
         .iter_mut()
         .par_bridge()
         .for_each(|body| fft.process(body));
+    #[cfg(target_arch = "wasm32")]
+    freqs.iter_mut().for_each(|body| fft.process(body));
 
     freqs
         .into_iter()
@@ -174,6 +241,7 @@ pub fn inverse_analyze(frames: usize, body: &BakedBody) -> Vec<DVec2> {
         .collect()
 }
 
+#[derive(Clone, Debug, Serialize)]
 pub struct BakedOrbit {
     pub name: String,
     pub period: f64,
@@ -181,6 +249,7 @@ pub struct BakedOrbit {
     pub bodies: Vec<BakedBody>,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl BakedOrbit {
     pub fn to_doc(&self) -> RcDoc<()> {
         let name = RcDoc::text("\"").append(self.name.clone()).append("\"");
@@ -195,11 +264,12 @@ impl BakedOrbit {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct BakedBody {
     pub frequencies: Vec<FrequencyComponent>,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl BakedBody {
     pub fn to_doc(&self) -> RcDoc<()> {
         pretty_elm_struct([(
@@ -207,16 +277,78 @@ impl BakedBody {
             pretty_elm_list(self.frequencies.iter().map(FrequencyComponent::to_doc)),
         )])
     }
+}
 
-    pub fn optimize(&mut self, cutoff: f64) {
-        let original_length = self.frequencies.len();
-        self.frequencies.retain(|freq| freq.amplitude > cutoff);
-        let new_length = self.frequencies.len();
-        eprintln!("optimized #freqs from {original_length} to {new_length}",);
+// Because the DFT basis is orthonormal, dropping a component of amplitude
+// `a` adds exactly `a²` to its coordinate stream's mean-squared
+// reconstruction error (Parseval's theorem), so pruning reduces to discarding
+// the smallest squared amplitudes pooled across every body in the orbit.
+// Discards are chosen by sorted position rather than an `amplitude² > λ`
+// threshold so that exact ties (e.g. choreography orbits, where every body's
+// spectrum is identical) don't get all kept or all discarded together.
+pub fn optimize_bodies(bodies: &mut [BakedBody], target: OptimizeTarget) {
+    let original_length: usize = bodies.iter().map(|body| body.frequencies.len()).sum();
+
+    let mut entries: Vec<(usize, usize, f64)> = bodies
+        .iter()
+        .enumerate()
+        .flat_map(|(body_idx, body)| {
+            body.frequencies
+                .iter()
+                .enumerate()
+                .map(move |(freq_idx, freq)| (body_idx, freq_idx, freq.amplitude * freq.amplitude))
+        })
+        .collect();
+    entries.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+    let discard_count = match target {
+        OptimizeTarget::Error(target_rms) => discard_count_for_error(&entries, target_rms * target_rms),
+        OptimizeTarget::Components(target_count) => entries.len().saturating_sub(target_count),
+    };
+
+    let discarded_energy: f64 = entries[..discard_count].iter().map(|(_, _, energy)| energy).sum();
+    let mut discard: Vec<Vec<bool>> = bodies
+        .iter()
+        .map(|body| vec![false; body.frequencies.len()])
+        .collect();
+    for (body_idx, freq_idx, _) in &entries[..discard_count] {
+        discard[*body_idx][*freq_idx] = true;
+    }
+
+    for (body_idx, body) in bodies.iter_mut().enumerate() {
+        let mut freq_idx = 0;
+        body.frequencies.retain(|_| {
+            let keep = !discard[body_idx][freq_idx];
+            freq_idx += 1;
+            keep
+        });
     }
+
+    let new_length: usize = bodies.iter().map(|body| body.frequencies.len()).sum();
+    eprintln!(
+        "optimized #freqs from {original_length} to {new_length} (discarded RMS {})",
+        discarded_energy.sqrt()
+    );
 }
 
-#[derive(Clone, Debug)]
+// counts how many of the smallest squared amplitudes (in sorted order) can be
+// discarded before the accumulated energy would push the RMS error past
+// `sqrt(target_energy)`
+fn discard_count_for_error(entries: &[(usize, usize, f64)], target_energy: f64) -> usize {
+    let mut discarded_energy = 0.0;
+    let mut count = 0;
+    for (_, _, energy) in entries {
+        if discarded_energy + energy > target_energy {
+            break;
+        }
+        discarded_energy += energy;
+        count += 1;
+    }
+
+    count
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct FrequencyComponent {
     pub freq: f64,
     pub amplitude: f64,
@@ -234,7 +366,10 @@ impl FrequencyComponent {
         let theta = std::f64::consts::TAU * at * self.freq + self.phase;
         DVec2::from_angle(-theta) * self.amplitude
     }
+}
 
+#[cfg(not(target_arch = "wasm32"))]
+impl FrequencyComponent {
     pub fn to_doc(&self) -> RcDoc<()> {
         let fmt_real = |val: f64| {
             let precision = 100000000.0;
@@ -249,6 +384,7 @@ impl FrequencyComponent {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn pretty_elm_struct<'a>(
     fields: impl IntoIterator<Item = (&'static str, RcDoc<'a, ()>)>,
 ) -> RcDoc<'a, ()> {
@@ -259,10 +395,12 @@ pub fn pretty_elm_struct<'a>(
     RcDoc::text("{").append(pretty_elm_group(inner)).append("}")
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn pretty_elm_list<'a>(items: impl IntoIterator<Item = RcDoc<'a, ()>>) -> RcDoc<'a, ()> {
     RcDoc::text("[").append(pretty_elm_group(items)).append("]")
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn pretty_elm_group<'a>(items: impl IntoIterator<Item = RcDoc<'a, ()>>) -> RcDoc<'a, ()> {
     let items = items.into_iter().map(|doc| doc.nest(2));
     let separator = RcDoc::hardline().flat_alt(RcDoc::nil()).append(", ");
@@ -274,14 +412,27 @@ pub fn pretty_elm_group<'a>(items: impl IntoIterator<Item = RcDoc<'a, ()>>) -> R
 }
 
 pub fn simulate_closed(config: &SimulationConfig, orbit: &Orbit) -> Vec<Vec<DVec2>> {
+    // the forward/backward blend corrects accumulated drift; the adaptive
+    // integrator below already bounds that drift directly, so blending is
+    // an optional extra polish rather than a correctness requirement
+    if !config.blend_time_reversed {
+        let mut forwards = simulate(config, orbit);
+        forwards.pop();
+        return forwards;
+    }
+
     let mut reversed = orbit.clone();
 
     for body in reversed.initial_conds.iter_mut() {
         body.velocity = -body.velocity;
     }
 
+    // same rayon-needs-threads caveat as in `analyze`
+    #[cfg(not(target_arch = "wasm32"))]
     let (mut forwards, mut backwards) =
         rayon::join(|| simulate(config, orbit), || simulate(config, &reversed));
+    #[cfg(target_arch = "wasm32")]
+    let (mut forwards, mut backwards) = (simulate(config, orbit), simulate(config, &reversed));
 
     backwards.reverse();
 
@@ -312,6 +463,11 @@ pub fn simulate_closed(config: &SimulationConfig, orbit: &Orbit) -> Vec<Vec<DVec
     forwards
 }
 
+// bounds on the adaptive inner substep count, so a frame that can't reach
+// the energy-drift tolerance can't loop forever doubling its step count
+const MIN_INNER_STEPS: usize = 1;
+const MAX_INNER_STEPS: usize = 1 << 16;
+
 pub fn simulate(config: &SimulationConfig, orbit: &Orbit) -> Vec<Vec<DVec2>> {
     let frame_num = config.frames * config.subframes;
     let timestep = orbit.period / frame_num as f64;
@@ -321,10 +477,35 @@ pub fn simulate(config: &SimulationConfig, orbit: &Orbit) -> Vec<Vec<DVec2>> {
     let first: Vec<_> = bodies.iter().map(|body| body.position).collect();
     history.push(first.clone());
 
+    let initial_energy = hamiltonian(&bodies);
+    let mut inner_steps = config.integrator.initial_inner_steps();
+    let mut drift = 0.0;
+
     let mut last = vec![];
     for _ in 0..frame_num {
-        for _ in 0..10000 {
-            step(timestep / 10000.0, &mut bodies);
+        loop {
+            let mut trial = bodies.clone();
+            let inner_dt = timestep / inner_steps as f64;
+
+            for _ in 0..inner_steps {
+                step(inner_dt, &mut trial, config.integrator);
+            }
+
+            drift = ((hamiltonian(&trial) - initial_energy) / initial_energy).abs();
+
+            if drift <= config.energy_drift_tolerance || inner_steps >= MAX_INNER_STEPS {
+                bodies = trial;
+
+                // plenty of headroom: halve the substep count for the next
+                // frame so accuracy isn't paid for needlessly
+                if drift <= config.energy_drift_tolerance * 0.1 && inner_steps > MIN_INNER_STEPS {
+                    inner_steps = (inner_steps / 2).max(MIN_INNER_STEPS);
+                }
+
+                break;
+            }
+
+            inner_steps *= 2;
         }
 
         last = bodies.iter().map(|body| body.position).collect();
@@ -332,10 +513,33 @@ pub fn simulate(config: &SimulationConfig, orbit: &Orbit) -> Vec<Vec<DVec2>> {
     }
 
     eprintln!("start-end simulation drift: {}", rms_error(&first, &last));
+    eprintln!("achieved relative energy drift: {drift} ({inner_steps} inner steps/frame)");
 
     history
 }
 
+// kinetic ½Σ|v|² plus pairwise gravitational potential -Σ mᵢmⱼ/rᵢⱼ; a good
+// integrator keeps this nearly constant, so its drift is what `simulate`
+// adapts the inner step size against. `apply_forces` updates `velocity` by
+// `dt * (mᵢ*mⱼ/r²)`, i.e. unweighted by the body's own mass, so the kinetic
+// term here must stay unweighted too or it isn't the conserved quantity
+pub fn hamiltonian(bodies: &[Body]) -> f64 {
+    let kinetic: f64 = bodies
+        .iter()
+        .map(|body| 0.5 * body.velocity.length_squared())
+        .sum();
+
+    let mut potential = 0.0;
+    for i in 0..bodies.len() {
+        for j in (i + 1)..bodies.len() {
+            let r = (bodies[i].position - bodies[j].position).length();
+            potential -= bodies[i].mass * bodies[j].mass / r;
+        }
+    }
+
+    kinetic + potential
+}
+
 pub fn rms_error(lhs: &[DVec2], rhs: &[DVec2]) -> f64 {
     lhs.iter()
         .zip(rhs.iter())
@@ -355,6 +559,7 @@ pub fn transpose<T, O: Clone>(positions: &[Vec<T>], map: impl Fn(&T) -> O) -> Ve
     by_body
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn render(config: &SimulationConfig, orbit: &Orbit, positions: &[Vec<DVec2>]) {
     let width = 400;
     let height = 400;
@@ -390,10 +595,106 @@ pub fn render(config: &SimulationConfig, orbit: &Orbit, positions: &[Vec<DVec2>]
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct SimulationConfig {
     pub frames: usize,
     pub subframes: usize,
+    pub integrator: Integrator,
+    // max tolerated relative Hamiltonian drift from the initial energy,
+    // checked at every frame boundary; `simulate` adapts its inner substep
+    // count to stay under this
+    pub energy_drift_tolerance: f64,
+    // blends a forward and time-reversed run to cancel residual drift;
+    // optional now that the integrator bounds drift itself
+    pub blend_time_reversed: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    Euler,
+    ForestRuth,
+}
+
+impl Integrator {
+    // starting guess for per-frame inner substeps, refined every frame by
+    // the adaptive energy-drift loop in `simulate`
+    fn initial_inner_steps(self) -> usize {
+        match self {
+            Integrator::Euler => 1000,
+            Integrator::ForestRuth => 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AudioConfig {
+    pub sample_rate: u32,
+    pub duration: f64,
+    pub base_frequency: f64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_audio(audio_config: &AudioConfig, orbit: &Orbit, bodies: &[BakedBody]) {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: audio_config.sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+
+    let path = format!("target/{}.wav", orbit.name);
+    let mut writer = WavWriter::create(&path, spec).unwrap();
+
+    let sample_num = (audio_config.sample_rate as f64 * audio_config.duration) as usize;
+
+    // pan bodies evenly across the stereo field, from hard left to hard right
+    let pans: Vec<f64> = if bodies.len() > 1 {
+        (0..bodies.len())
+            .map(|idx| idx as f64 / (bodies.len() - 1) as f64)
+            .collect()
+    } else {
+        vec![0.5]
+    };
+
+    let mut samples = vec![[0.0f64; 2]; sample_num];
+    for (body, pan) in bodies.iter().zip(pans.iter()) {
+        for (idx, sample) in samples.iter_mut().enumerate() {
+            let t = idx as f64 / audio_config.sample_rate as f64;
+
+            // `freq.freq` is a cycle count per orbital period, so scaling
+            // it directly by the base pitch (the period cancels out)
+            // preserves the integer harmonic ratios between components
+            let value: f64 = body
+                .frequencies
+                .iter()
+                .map(|freq| {
+                    let hz = freq.freq * audio_config.base_frequency;
+                    freq.amplitude * (std::f64::consts::TAU * hz * t + freq.phase).sin()
+                })
+                .sum();
+
+            sample[0] += value * (1.0 - pan);
+            sample[1] += value * pan;
+        }
+    }
+
+    // normalize so the loudest stacked sample lands exactly at full scale
+    let peak = samples
+        .iter()
+        .flat_map(|sample| sample.iter().copied())
+        .fold(0.0f64, |max, value| max.max(value.abs()))
+        .max(1e-9);
+
+    for sample in samples {
+        for channel in sample {
+            writer
+                .write_sample((channel / peak * i16::MAX as f64) as i16)
+                .unwrap();
+        }
+    }
+
+    writer.finalize().unwrap();
 }
 
 #[derive(Clone, Debug)]
@@ -403,6 +704,7 @@ pub struct Orbit {
     pub period: f64,
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 pub fn draw(dt: &mut DrawTarget, bodies: &[Body], positions: &[DVec2]) {
     for (body, position) in bodies.iter().zip(positions.iter()) {
         let mut pb = PathBuilder::new();
@@ -419,9 +721,35 @@ pub fn draw(dt: &mut DrawTarget, bodies: &[Body], positions: &[DVec2]) {
     }
 }
 
-pub fn step(dt: f64, bodies: &mut [Body]) {
-    apply_forces(dt, bodies);
+pub fn step(dt: f64, bodies: &mut [Body], integrator: Integrator) {
+    match integrator {
+        Integrator::Euler => {
+            apply_forces(dt, bodies);
+            update(dt, bodies);
+        }
+        Integrator::ForestRuth => step_forest_ruth(dt, bodies),
+    }
+}
+
+// one symmetric kick-drift-kick (leapfrog) step of size `dt`; `step_forest_ruth`
+// composes three of these with Yoshida's weights to reach 4th order
+fn step_leapfrog(dt: f64, bodies: &mut [Body]) {
+    apply_forces(dt / 2.0, bodies);
     update(dt, bodies);
+    apply_forces(dt / 2.0, bodies);
+}
+
+// 4th-order Yoshida/Forest-Ruth composition: three leapfrog substeps of
+// size w1*dt, w0*dt, w1*dt, with w1 = 1 / (2 - 2^(1/3)) and
+// w0 = -2^(1/3) / (2 - 2^(1/3))
+fn step_forest_ruth(dt: f64, bodies: &mut [Body]) {
+    let cbrt2 = 2f64.powf(1.0 / 3.0);
+    let w1 = 1.0 / (2.0 - cbrt2);
+    let w0 = -cbrt2 / (2.0 - cbrt2);
+
+    step_leapfrog(w1 * dt, bodies);
+    step_leapfrog(w0 * dt, bodies);
+    step_leapfrog(w1 * dt, bodies);
 }
 
 pub fn apply_forces(dt: f64, bodies: &mut [Body]) {